@@ -1,4 +1,4 @@
-use std::{collections::{HashMap, HashSet}, error::Error, fs::File, io::{BufReader, Write}, marker::PhantomData, path::PathBuf, sync::atomic::{AtomicUsize, Ordering}};
+use std::{collections::{HashMap, HashSet}, error::Error, fs::File, io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write}, marker::PhantomData, path::PathBuf, sync::atomic::{AtomicUsize, Ordering}};
 use colored::Colorize;
 use liblisa::{FilterMap, enumeration::{EnumWorker, RuntimeWorkerData}, synthesis::preprocess_encodings, work::Work};
 use lisacli::SavePath;
@@ -6,6 +6,13 @@ use structopt::StructOpt;
 use liblisa_x64::{arch::X64Arch, x64_kmod_ptrace_oracle};
 use liblisa_core::arch::{Arch, Instruction, InstructionInfo};
 use liblisa_core::counter::InstructionCounter;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use flate2::read::ZlibDecoder;
+use serde::{Serialize, Deserialize};
 use itertools::Itertools;
 use rayon::prelude::*;
 
@@ -47,7 +54,39 @@ enum Verb {
     #[structopt(help = "Rebuilds all filters based on the encodings found.")]
     RebuildFilters,
 
+    #[structopt(help = "(Re)builds the on-disk prefix index over encodings that Status --scan uses for per-instruction lookups. Dump does not read it.")]
+    Reindex,
+
     Extract { path: PathBuf },
+
+    #[structopt(help = "Re-evaluates found encodings against a freshly queried oracle to catch regressions. Checkpointable and resumable.")]
+    Verify {
+        #[structopt(long = "quick", help = "Sample a random subset instead of walking every encoding.")]
+        quick: bool,
+
+        #[structopt(long = "sample", default_value = "10000", help = "Number of encodings to sample in --quick mode.")]
+        sample: usize,
+
+        #[structopt(long = "checkpoint-every", default_value = "1000", help = "Persist a resume checkpoint after this many encodings.")]
+        checkpoint_every: usize,
+    },
+
+    #[structopt(help = "Generates a standalone Rust decoder source file from the discovered encodings.")]
+    GenerateDecoder {
+        #[structopt(long = "out")]
+        out: PathBuf,
+    },
+
+    #[structopt(help = "Packs all found encodings into a compressed, checksummed binary artifact file.")]
+    Pack { path: PathBuf },
+
+    #[structopt(help = "Lists the encodings stored in a pack file produced by `Pack`.")]
+    Unpack {
+        path: PathBuf,
+
+        #[structopt(long = "instr", help = "Only decompress the single block covering this hex instruction prefix (e.g. 0F05).")]
+        instr: Option<String>,
+    },
 }
 
 #[derive(StructOpt)]
@@ -65,8 +104,379 @@ struct Stats {
     total: usize,
 }
 
+// Pack format ----------------------------------------------------------------
+//
+// `serde_json` is convenient but enormous for the millions of encodings a full
+// enumeration produces. A pack file is a much denser binary container: a small
+// header followed by a run of fixed-size blocks, each holding `PACK_BLOCK_SIZE`
+// encodings serialized with `bincode` and then zlib-compressed. Every block is
+// prefixed with the uncompressed payload length and a CRC32 of that payload,
+// XOR'd with a per-block-type tag so a data block can never be silently decoded
+// as the index block (or vice versa). A trailing index block maps the smallest
+// matching instruction of each data block to its file offset, and the very last
+// 8 bytes of the file point at that index block.
+//
+// `Pack` sorts encodings by their smallest matching instruction before blocking
+// them, so each block holds a contiguous instruction range and the index seek is
+// range-local. It is still a seek hint, not an exact lookup: an encoding with a
+// broad filter matches instructions well past its own smallest one and can land
+// in an earlier block, so `Unpack --instr` can still miss it. Decode the full
+// pack when you need an exhaustive answer.
+
+const PACK_MAGIC: u64 = 0x6c69_7361_706b_3031; // "lisapk01"
+const PACK_VERSION: u64 = 1;
+const PACK_BLOCK_SIZE: usize = 4096;
+
+const BLOCK_TAG_SUPER: u32 = 0x5350_4552; // header/superblock
+const BLOCK_TAG_DATA: u32 = 0x4441_5441; // run of encodings
+const BLOCK_TAG_INDEX: u32 = 0x4944_5820; // tail index
+
+#[derive(Serialize, Deserialize)]
+struct PackHeader {
+    magic: u64,
+    version: u64,
+    block_size: u64,
+}
+
+/// Maps the smallest matching instruction of each data block to its offset in
+/// the file. `Pack` sorts the entries by instruction before writing so a prefix
+/// lookup in `Unpack` is a binary search.
+#[derive(Serialize, Deserialize, Default)]
+struct PackIndex {
+    blocks: Vec<(Instruction, u64)>,
+}
+
+/// Parses a hex byte string like `0F05` into its bytes.
+fn parse_hex(s: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let s = s.trim();
+    if s.len() % 2 != 0 {
+        return Err("hex instruction must have an even number of digits".into());
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).map_err(|e| e.into()))
+        .collect()
+}
+
+/// Writes a single block: `u64` uncompressed length, `u32` tagged CRC32, `u64`
+/// compressed length, then the zlib-compressed payload.
+fn write_block<W: Write>(w: &mut W, tag: u32, payload: &[u8]) -> io::Result<()> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(payload)?;
+    let compressed = encoder.finish()?;
+
+    let crc = crc32fast::hash(payload) ^ tag;
+    w.write_all(&(payload.len() as u64).to_le_bytes())?;
+    w.write_all(&crc.to_le_bytes())?;
+    w.write_all(&(compressed.len() as u64).to_le_bytes())?;
+    w.write_all(&compressed)?;
+    Ok(())
+}
+
+/// Reads a block written by `write_block`, verifying that the stored CRC (once
+/// the block-type tag is XOR'd back out) matches the decompressed payload.
+fn read_block<R: Read>(r: &mut R, tag: u32) -> io::Result<Vec<u8>> {
+    let mut buf8 = [0u8; 8];
+    r.read_exact(&mut buf8)?;
+    let uncompressed_len = u64::from_le_bytes(buf8) as usize;
+
+    let mut buf4 = [0u8; 4];
+    r.read_exact(&mut buf4)?;
+    let crc = u32::from_le_bytes(buf4);
+
+    r.read_exact(&mut buf8)?;
+    let compressed_len = u64::from_le_bytes(buf8) as usize;
+
+    let mut compressed = vec![0u8; compressed_len];
+    r.read_exact(&mut compressed)?;
+
+    let mut payload = Vec::with_capacity(uncompressed_len);
+    ZlibDecoder::new(&compressed[..]).read_to_end(&mut payload)?;
+
+    if payload.len() != uncompressed_len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "block length mismatch"));
+    }
+    if crc32fast::hash(&payload) ^ tag != crc {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "block checksum mismatch (wrong block type or corrupt data)"));
+    }
+
+    Ok(payload)
+}
+
+// Work chunks -----------------------------------------------------------------
+//
+// Instruction density is wildly uneven: large opcode regions are empty while a
+// handful explode. Handing each worker one contiguous `[from, to)` range
+// therefore leaves some workers idle within minutes while others grind for
+// days. Instead we slice the whole space into many small chunks, shuffle them
+// so dense and sparse regions are spread across all workers, and keep them in a
+// shared queue persisted alongside the enumeration state. A worker pulls the
+// next unclaimed chunk whenever its current one is exhausted.
+
+const CHUNK_QUEUE_FILE: &str = "chunks.json";
+
+/// The instruction-bitstring prefix (in bits) chunk boundaries are cut on. A
+/// 16-bit opcode prefix gives 65536 evenly-spaced boundaries to divide among
+/// workers, which is plenty of chunks for load balancing without being unbounded.
+const CHUNK_PREFIX_BITS: u32 = 16;
+
+/// Lifecycle of a single chunk. A finished chunk becomes `Done` (not
+/// `Unclaimed`) so it is neither re-enumerated nor reported as outstanding.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+enum ChunkState {
+    Unclaimed,
+    Claimed(usize),
+    Done(usize),
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ChunkQueue {
+    chunk_size: usize,
+    /// Shuffled `(from, to)` ranges covering the whole instruction space.
+    chunks: Vec<(Instruction, Option<Instruction>)>,
+    /// Per-chunk lifecycle state, indexed in lockstep with `chunks`.
+    state: Vec<ChunkState>,
+}
+
+impl ChunkQueue {
+    fn path(dir: &std::path::Path) -> PathBuf {
+        dir.join(CHUNK_QUEUE_FILE)
+    }
+
+    fn load(dir: &std::path::Path) -> Result<Option<ChunkQueue>, Box<dyn Error>> {
+        let path = ChunkQueue::path(dir);
+        if path.exists() {
+            Ok(Some(serde_json::from_reader(BufReader::new(File::open(path)?))?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn save(&self, dir: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(File::create(ChunkQueue::path(dir))?, self)?;
+        Ok(())
+    }
+
+    /// Claims the next still-`Unclaimed` chunk for `worker`, returning its range.
+    /// `Claimed` and `Done` chunks are skipped, so a worker always pulls fresh
+    /// work rather than re-claiming the chunk it just finished.
+    fn claim_next(&mut self, worker: usize) -> Option<(Instruction, Option<Instruction>)> {
+        let index = self.state.iter().position(|s| *s == ChunkState::Unclaimed)?;
+        self.state[index] = ChunkState::Claimed(worker);
+        Some(self.chunks[index].clone())
+    }
+
+    /// Marks the chunk `worker` was enumerating as `Done` so it is not handed out
+    /// again. Called when a worker exhausts its current chunk.
+    fn complete(&mut self, worker: usize) {
+        for state in self.state.iter_mut() {
+            if *state == ChunkState::Claimed(worker) {
+                *state = ChunkState::Done(worker);
+            }
+        }
+    }
+
+    /// Returns a claimed-but-unfinished chunk held by `worker` to the pool.
+    /// Reserved for `ResetWorker`/abort; `Done` chunks are left untouched.
+    fn release(&mut self, worker: usize) {
+        for state in self.state.iter_mut() {
+            if *state == ChunkState::Claimed(worker) {
+                *state = ChunkState::Unclaimed;
+            }
+        }
+    }
+
+    fn count(&self, pred: impl Fn(&ChunkState) -> bool) -> usize {
+        self.state.iter().filter(|s| pred(s)).count()
+    }
+}
+
+/// Maps an instruction to its `CHUNK_PREFIX_BITS`-wide big-endian prefix key,
+/// zero-padding instructions shorter than the prefix width.
+fn chunk_prefix_key(instr: &Instruction) -> u64 {
+    let width = (CHUNK_PREFIX_BITS / 8) as usize;
+    let mut key = 0u64;
+    for i in 0..width {
+        key = (key << 8) | *instr.bytes().get(i).unwrap_or(&0) as u64;
+    }
+    key
+}
+
+/// Turns a prefix key back into the instruction at the start of that prefix.
+fn chunk_prefix_instr(key: u64) -> Instruction {
+    let width = (CHUNK_PREFIX_BITS / 8) as usize;
+    let bytes = (0..width).rev().map(|i| (key >> (i * 8)) as u8).collect::<Vec<_>>();
+    Instruction::new(&bytes)
+}
+
+/// Builds the shuffled chunk queue. Boundaries are cut on the instruction
+/// *bitstring* prefix space (not on indices into the seed list, which would
+/// yield only a couple of chunks), bounded to the span the seeds cover.
+/// `chunk_size` clamps the request's formula so a small enumeration still gets
+/// reasonably sized chunks and a huge one does not produce unbounded chunks.
+fn build_chunk_queue(work: &[Instruction], num_workers: usize) -> ChunkQueue {
+    let first = work.iter().map(chunk_prefix_key).min().unwrap_or(0);
+    let last = work.iter().map(chunk_prefix_key).max().unwrap_or((1 << CHUNK_PREFIX_BITS) - 1);
+    let total = (last - first + 1).max(1) as usize;
+    // Aim for ~64 chunks per worker, clamped for sanity, but never coarser than
+    // one chunk per worker: if chunks < workers, `Create` cannot seed every
+    // worker from the queue and the unseeded ones fall back to the overlapping
+    // baseline range, re-enumerating chunked space and producing duplicate
+    // artifacts. `num_workers` is always >= 1, so the divisor is safe.
+    let chunk_size = (total / (num_workers * 64))
+        .clamp(128, 4096)
+        .min((total / num_workers).max(1));
+
+    let mut chunks = Vec::new();
+    let mut start = first;
+    while start <= last {
+        let end = (start + chunk_size as u64).min(last + 1);
+        let from = chunk_prefix_instr(start);
+        // The final chunk is open-ended so it also covers any instruction bytes
+        // below the last seed's prefix; interior chunks stop at the next boundary.
+        let to = if end > last { None } else { Some(chunk_prefix_instr(end)) };
+        chunks.push((from, to));
+        start = end;
+    }
+
+    let mut rng = StdRng::from_entropy();
+    chunks.shuffle(&mut rng);
+
+    let state = vec![ChunkState::Unclaimed; chunks.len()];
+    ChunkQueue { chunk_size, chunks, state }
+}
+
+// Scrub/verify ----------------------------------------------------------------
+//
+// A full pass over every artifact takes a very long time, so `Verify` writes a
+// cursor plus a running pass/fail tally into the save directory after every N
+// encodings. Interrupting and restarting resumes from the last checkpoint
+// instead of starting over. The checkpoint also records the sampled index set
+// for `--quick` runs so a sampled scrub resumes over the same subset.
+
+const SCRUB_CHECKPOINT_FILE: &str = "scrub.json";
+
+#[derive(Serialize, Deserialize, Default)]
+struct ScrubCheckpoint {
+    /// Position into the scrub queue (not directly into `artifacts()`).
+    cursor: usize,
+    found: usize,
+    missed: usize,
+    total: usize,
+    /// The sampled artifact indices for a `--quick` scrub, or `None` for a full
+    /// scrub (which walks `0..artifacts().len()`).
+    sampled: Option<Vec<usize>>,
+}
+
+/// A comparable projection of an encoding's *computed* output semantics: for
+/// each output, its full dataflow -- the target location, the inputs feeding it
+/// and the computation over them -- rendered structurally. Comparing only
+/// `(memory_access, num_inputs)` treated two encodings that read the same arity
+/// of inputs into the same kind of target as identical even when the dataflow
+/// itself differed, so a regression that rewired inputs or changed the
+/// computation slipped through. Rendering the dataflow catches those while still
+/// ignoring the internal bookkeeping fields a whole-struct `PartialEq` trips on.
+///
+/// The rendered outputs are sorted so the comparison is a multiset: preprocessing
+/// may list the same outputs in a different order, which is not a semantic change
+/// and must not be reported as a regression.
+fn output_semantics<A: Arch>(encoding: &liblisa::Encoding<A>) -> Vec<String> {
+    let mut rendered: Vec<String> = encoding.outputs().map(|o| format!("{:?}", o)).collect();
+    rendered.sort();
+    rendered
+}
+
+// Prefix index ----------------------------------------------------------------
+//
+// Rebuilding a `FilterMap` from every artifact on each `Status --scan` is
+// increasingly expensive as the artifact set grows. The prefix index is a
+// sorted, column-style on-disk store that keys each encoding by the big-endian
+// bytes of its smallest matching instruction (plus a disambiguator so two
+// encodings sharing a prefix stay distinct). A range query over the sorted keys
+// yields the handful of candidate encodings whose prefix matches an instruction,
+// so lookups materialize only those instead of the whole set.
+
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    /// Big-endian smallest matching instruction: the prefix key.
+    key: Vec<u8>,
+    /// Distinguishes encodings that share a prefix key.
+    disambiguator: u32,
+    /// Position of the encoding within `runner.artifacts()`.
+    offset: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct EncodingIndex {
+    /// On-disk format version. Bumped whenever the layout below changes so a
+    /// file written by an older build is ignored rather than misparsed into
+    /// plausible-but-wrong offsets.
+    version: u32,
+    /// Number of artifacts the index was built against. A mismatch with the
+    /// current artifact set means the index is stale (Reindex was not re-run)
+    /// and its offsets may be out of range, so it must not be trusted.
+    artifact_count: usize,
+    /// Sorted by `(key, disambiguator)` so a prefix is a contiguous range.
+    entries: Vec<IndexEntry>,
+}
+
+impl EncodingIndex {
+    const FILE: &'static str = "encodings.idx";
+    const FORMAT_VERSION: u32 = 1;
+
+    fn path(dir: &std::path::Path) -> PathBuf {
+        dir.join(EncodingIndex::FILE)
+    }
+
+    fn load(dir: &std::path::Path) -> Result<Option<EncodingIndex>, Box<dyn Error>> {
+        let path = EncodingIndex::path(dir);
+        if !path.exists() {
+            return Ok(None);
+        }
+        // A file written by an older build can neither be trusted nor cleanly
+        // decoded, so a decode failure or version mismatch is treated as "no
+        // usable index" (fall back to the FilterMap) rather than a hard error.
+        match bincode::deserialize_from::<_, EncodingIndex>(BufReader::new(File::open(path)?)) {
+            Ok(index) if index.version == EncodingIndex::FORMAT_VERSION => Ok(Some(index)),
+            Ok(index) => {
+                println!("Ignoring encoding index written in format v{} (expected v{}); run Reindex.", index.version, EncodingIndex::FORMAT_VERSION);
+                Ok(None)
+            }
+            Err(e) => {
+                println!("Ignoring unreadable encoding index ({}); run Reindex.", e);
+                Ok(None)
+            }
+        }
+    }
+
+    fn save(&self, dir: &std::path::Path) -> Result<(), Box<dyn Error>> {
+        bincode::serialize_into(BufWriter::new(File::create(EncodingIndex::path(dir))?), self)?;
+        Ok(())
+    }
+
+    /// Returns the entries whose key begins with `prefix`, via binary search.
+    fn range(&self, prefix: &[u8]) -> &[IndexEntry] {
+        let lo = self.entries.partition_point(|e| e.key.as_slice() < prefix);
+        let hi = lo + self.entries[lo..].partition_point(|e| e.key.starts_with(prefix));
+        &self.entries[lo..hi]
+    }
+
+    /// Finds the encoding offset matching `instr`, materializing only the
+    /// candidates that share its leading opcode byte and returning the one whose
+    /// canonical prefix is the longest (most specific) prefix of `instr`.
+    fn lookup(&self, instr: &[u8]) -> Option<u64> {
+        let prefix = &instr[..instr.len().min(1)];
+        self.range(prefix)
+            .iter()
+            .filter(|e| instr.starts_with(&e.key))
+            .max_by_key(|e| e.key.len())
+            .map(|e| e.offset)
+    }
+}
+
 fn run() -> Result<(), Box<dyn Error>> {
     let args = Args::from_args();
+    let dir = args.dir.clone();
     let save_paths = SavePath::from(args.dir);
     match args.verb {
         Verb::Create { num_workers: threads, scan } => {
@@ -106,6 +516,9 @@ fn run() -> Result<(), Box<dyn Error>> {
                 (0..=255u8).map(|i| Instruction::new(&[ i ])).collect::<Vec<_>>()
             };
 
+            let mut queue = build_chunk_queue(&work, threads);
+            println!("Divided the instruction space into {} chunks of ~{} bitstring steps each", queue.chunks.len(), queue.chunk_size);
+
             Work::create(save_paths, &work, threads, |from, to| {
                 EnumWorker {
                     counter: InstructionCounter::range(from.as_instr(), to.cloned()),
@@ -118,11 +531,64 @@ fn run() -> Result<(), Box<dyn Error>> {
                 }
             })?;
 
+            // Seed each worker from the queue keyed by its *real* id (the synthetic
+            // creation order is not guaranteed to equal the runtime worker id), so
+            // later `release`/`complete` calls target the right claim set.
+            let mut runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(SavePath::from(dir.clone()))?;
+            for worker in runner.workers_mut().iter_mut() {
+                let id = *worker.id();
+                if let Some((from, to)) = queue.claim_next(id) {
+                    worker.inner_mut().counter = InstructionCounter::range(from.as_instr(), to);
+                }
+            }
+            queue.save(&dir)?;
+            runner.save_all()?;
+
             println!("State created!");
         }
         Verb::Run => {
             let mut runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
-            runner.run(&RuntimeWorkerData::new())?;
+            let data = RuntimeWorkerData::new();
+
+            // `runner.run` returns once every worker has exhausted its current
+            // chunk. If a shared chunk queue exists, hand each finished worker the
+            // next unclaimed chunk and run again, so a worker that drains a sparse
+            // region immediately steals more work instead of idling. This is the
+            // work-stealing loop the contiguous-range baseline lacked.
+            loop {
+                runner.run(&data)?;
+
+                let mut queue = match ChunkQueue::load(&dir)? {
+                    Some(queue) => queue,
+                    None => break,
+                };
+
+                let mut assigned_any = false;
+                for worker in runner.workers_mut().iter_mut() {
+                    if !worker.done() {
+                        continue;
+                    }
+
+                    // Mark the just-finished chunk Done (never release it -- releasing
+                    // would make claim_next hand the same chunk straight back, looping
+                    // forever), then pull the next genuinely unclaimed chunk.
+                    let id = *worker.id();
+                    queue.complete(id);
+                    if let Some((from, to)) = queue.claim_next(id) {
+                        worker.inner_mut().counter = InstructionCounter::range(from.as_instr(), to);
+                        worker.inner_mut().instrs_seen.clear();
+                        worker.reset_done();
+                        assigned_any = true;
+                    }
+                }
+
+                queue.save(&dir)?;
+                runner.save_all()?;
+
+                if !assigned_any {
+                    break;
+                }
+            }
         }
         Verb::Status { scan } => {
             let runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
@@ -130,22 +596,34 @@ fn run() -> Result<(), Box<dyn Error>> {
             let unique_sequences: u128 = workers.iter().map(|s| s.inner().unique_sequences).sum();
             let seconds_running: u64 = runner.seconds_running();
 
+            // Prefer the on-disk prefix index when it exists: it lets us range-query
+            // candidates per instruction instead of rebuilding a full FilterMap.
+            // Ignore a stale index (built against a different artifact count), whose
+            // offsets could be out of range and panic in the scan hot loop below.
+            let encoding_index = EncodingIndex::load(&dir)?.filter(|index| {
+                if index.artifact_count == runner.artifacts().len() {
+                    true
+                } else {
+                    println!("Ignoring stale encoding index ({} entries for {} artifacts, now {}); run Reindex.", index.entries.len(), index.artifact_count, runner.artifacts().len());
+                    false
+                }
+            });
             let mut filter_map = FilterMap::new();
-            let num_encodings = {
-                let encodings = if scan.is_some() {
-                    println!("Loading encodings...");
-                    runner.artifacts().iter().collect::<Vec<_>>()
-                } else { 
-                    Vec::new()
-                };
-
+            let num_encodings = if scan.is_none() {
+                0
+            } else if encoding_index.is_some() {
+                println!("Using on-disk encoding index...");
+                runner.artifacts().len()
+            } else {
+                println!("Loading encodings...");
+                let encodings = runner.artifacts().iter().collect::<Vec<_>>();
                 for (index, e) in encodings.iter().enumerate() {
                     let filters = e.filters();
 
                     if filters.len() <= 0 {
                         panic!("No filters for {}", e);
                     }
-                    
+
                     for filter in filters {
                         filter_map.add(filter, index);
                     }
@@ -169,9 +647,14 @@ fn run() -> Result<(), Box<dyn Error>> {
                                 .unwrap();
     
                             let mut match_found = false;
-                            if let Some(index) = filter_map.filters(instr.as_instr()) {
+                            let hit = if let Some(index) = &encoding_index {
+                                index.lookup(instr.bytes()).map(|offset| offset as usize)
+                            } else {
+                                filter_map.filters(instr.as_instr()).copied()
+                            };
+                            if let Some(offset) = hit.filter(|&o| o < encodings_seen.len()) {
                                 match_found = true;
-                                encodings_seen[*index] = true;
+                                encodings_seen[offset] = true;
                             }
                             
                             if match_found {
@@ -211,6 +694,15 @@ fn run() -> Result<(), Box<dyn Error>> {
             };
 
             println!("Found {} instruction encodings (=2^{:.2} bitstrings) in {}h {}m {}s (approx. 2^{:.2} bitstrings/hour)", runner.artifacts().len(), (unique_sequences as f64).log2(), seconds_running / 3600, (seconds_running / 60) % 60, seconds_running % 60, (unique_sequences as f64 / (seconds_running as f64 / (3600.0))).log2());
+
+            // Per-chunk progress, aggregated per worker for the summary below.
+            let chunk_queue = ChunkQueue::load(&dir)?;
+            if let Some(queue) = &chunk_queue {
+                let done = queue.count(|s| matches!(s, ChunkState::Done(_)));
+                let claimed = queue.count(|s| matches!(s, ChunkState::Claimed(_)));
+                let waiting = queue.count(|s| *s == ChunkState::Unclaimed);
+                println!("Chunk queue: {} / {} done, {} in progress, {} waiting to be stolen (chunk size ~{})", done, queue.chunks.len(), claimed, waiting, queue.chunk_size);
+            }
             println!();
 
             let current_pad = workers.iter().map(|s| s.inner().counter.current().bytes().len() * 4).max().unwrap();
@@ -232,6 +724,13 @@ fn run() -> Result<(), Box<dyn Error>> {
                     print!("@ {:pad$}: ", format!("{:02X?}", worker.inner().counter.current().bytes()).bold(), pad = current_pad);
                 }
 
+                if let Some(queue) = &chunk_queue {
+                    let id = *worker.id();
+                    let done = queue.count(|s| *s == ChunkState::Done(id));
+                    let active = queue.count(|s| *s == ChunkState::Claimed(id));
+                    print!("[{} done, {} active chunk(s)] ", done, active);
+                }
+
                 println!("found {} encodings", worker.artifacts_produced());
             }
 
@@ -248,13 +747,30 @@ fn run() -> Result<(), Box<dyn Error>> {
         }
         Verb::ResetWorker { num } => {
             let mut runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
-            let workers = runner.workers_mut();
-            let worker = &mut workers[num];
 
-            let new_counter = InstructionCounter::range(worker.from().as_instr(), worker.to().clone());
-            worker.inner_mut().counter = new_counter;
-            worker.inner_mut().instrs_seen.clear();
-            worker.reset_done();
+            // In the chunked scheme a worker owns chunks rather than a fixed range:
+            // release the chunks it had claimed back to the shared queue and give it
+            // a fresh one to restart from, so the released chunks can be re-stolen
+            // (possibly by a different, idle worker).
+            if let Some(mut queue) = ChunkQueue::load(&dir)? {
+                queue.release(num);
+                let next = queue.claim_next(num);
+                queue.save(&dir)?;
+
+                let worker = &mut runner.workers_mut()[num];
+                let (from, to) = next
+                    .map(|(from, to)| (from.as_instr().clone(), to))
+                    .unwrap_or_else(|| (worker.from().as_instr().clone(), worker.to().clone()));
+                worker.inner_mut().counter = InstructionCounter::range(from.as_instr(), to);
+                worker.inner_mut().instrs_seen.clear();
+                worker.reset_done();
+            } else {
+                let worker = &mut runner.workers_mut()[num];
+                let new_counter = InstructionCounter::range(worker.from().as_instr(), worker.to().clone());
+                worker.inner_mut().counter = new_counter;
+                worker.inner_mut().instrs_seen.clear();
+                worker.reset_done();
+            }
 
             runner.save_all().unwrap();
         }
@@ -307,6 +823,33 @@ fn run() -> Result<(), Box<dyn Error>> {
 
             runner.save_all().unwrap();
         }
+        Verb::Reindex => {
+            let runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
+            let encodings = runner.artifacts().iter().collect::<Vec<_>>();
+
+            println!("Building encoding index...");
+            let mut entries = Vec::new();
+            for (offset, encoding) in encodings.iter().enumerate() {
+                if offset % 1000 == 0 {
+                    println!("{} / {}", offset, encodings.len());
+                }
+
+                for filter in encoding.filters() {
+                    entries.push(IndexEntry {
+                        key: filter.smallest_matching_instruction().bytes().to_vec(),
+                        disambiguator: offset as u32,
+                        offset: offset as u64,
+                    });
+                }
+            }
+
+            entries.sort_by(|a, b| a.key.cmp(&b.key).then(a.disambiguator.cmp(&b.disambiguator)));
+
+            let index = EncodingIndex { version: EncodingIndex::FORMAT_VERSION, artifact_count: encodings.len(), entries };
+            index.save(&dir)?;
+
+            println!("Indexed {} filters from {} encodings", index.entries.len(), encodings.len());
+        }
         Verb::ResumeWorker { num } => {
             let mut runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
             let workers = runner.workers_mut();
@@ -369,6 +912,259 @@ fn run() -> Result<(), Box<dyn Error>> {
             println!("Saving results...");
             serde_json::to_writer(File::create(path)?, &encodings)?;
         }
+        Verb::GenerateDecoder { out } => {
+            let runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
+            let encodings = runner.artifacts().iter().collect::<Vec<_>>();
+
+            // One semantic summary per encoding (not per row), so the generated
+            // table does not repeat the same string for every filter.
+            let summaries = encodings.iter().map(|encoding| {
+                let mem = encoding.outputs().filter(|o| o.memory_access).count();
+                let inputs = encoding.outputs().map(|o| o.num_inputs).max().unwrap_or(0);
+                format!("{} output(s), {} memory access(es), {} input(s)", encoding.outputs().count(), mem, inputs)
+            }).collect::<Vec<_>>();
+
+            // One row per filter, keyed by the bytes of its smallest matching
+            // instruction. Rows are sorted so all rows sharing a leading opcode
+            // byte are contiguous and the opcode table can point at a slice.
+            let mut rows = Vec::new();
+            for (index, encoding) in encodings.iter().enumerate() {
+                for filter in encoding.filters() {
+                    rows.push((filter.smallest_matching_instruction().bytes().to_vec(), index));
+                }
+            }
+            rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+            // Opcode table: leading byte -> (start, end) range into ROWS.
+            let mut opcode_table = [(0u32, 0u32); 256];
+            for op in 0..256usize {
+                let start = rows.partition_point(|(pattern, _)| pattern.first().map(|&b| (b as usize) < op).unwrap_or(true));
+                let end = rows.partition_point(|(pattern, _)| pattern.first().map(|&b| (b as usize) <= op).unwrap_or(true));
+                opcode_table[op] = (start as u32, end as u32);
+            }
+
+            println!("Writing {} decoder entries to {:?}...", rows.len(), out);
+            let mut w = BufWriter::new(File::create(&out)?);
+            writeln!(w, "// @generated by `enumerate GenerateDecoder` -- do not edit by hand.")?;
+            writeln!(w, "// A self-contained classifier built from enumerated encodings. It has no")?;
+            writeln!(w, "// dependency on liblisa, so downstream tools can classify instructions cheaply.")?;
+            writeln!(w, "//")?;
+            writeln!(w, "// Matching is by the canonical (smallest) instruction prefix of each filter,")?;
+            writeln!(w, "// dispatched through a 256-entry opcode table; it is a cheap prefix classifier")?;
+            writeln!(w, "// and does not reproduce liblisa's exact don't-care-bit FilterMap semantics.")?;
+            writeln!(w)?;
+            writeln!(w, "/// A single enumerated filter, reduced to the prefix needed to recognise it.")?;
+            writeln!(w, "pub struct Row {{")?;
+            writeln!(w, "    /// Canonical instruction-byte prefix that selects this encoding.")?;
+            writeln!(w, "    pub pattern: &'static [u8],")?;
+            writeln!(w, "    /// Index of the source encoding in the enumeration artifacts.")?;
+            writeln!(w, "    pub index: usize,")?;
+            writeln!(w, "}}")?;
+            writeln!(w)?;
+            writeln!(w, "pub static ROWS: &[Row] = &[")?;
+            for (pattern, index) in &rows {
+                writeln!(w, "    Row {{ pattern: &{:?}, index: {} }},", pattern, index)?;
+            }
+            writeln!(w, "];")?;
+            writeln!(w)?;
+            writeln!(w, "/// Maps a leading opcode byte to the `[start, end)` slice of ROWS for it.")?;
+            writeln!(w, "pub static OPCODE_TABLE: [(u32, u32); 256] = [")?;
+            for (start, end) in opcode_table.iter() {
+                writeln!(w, "    ({}, {}),", start, end)?;
+            }
+            writeln!(w, "];")?;
+            writeln!(w)?;
+            writeln!(w, "/// Short human-readable semantic summary per encoding index.")?;
+            writeln!(w, "pub static SUMMARIES: &[&str] = &[")?;
+            for summary in &summaries {
+                writeln!(w, "    {:?},", summary)?;
+            }
+            writeln!(w, "];")?;
+            writeln!(w)?;
+            writeln!(w, "/// Returns the most specific (longest prefix) encoding matching `instr`.")?;
+            writeln!(w, "pub fn decode(instr: &[u8]) -> Option<&'static Row> {{")?;
+            writeln!(w, "    let op = *instr.first()? as usize;")?;
+            writeln!(w, "    let (start, end) = OPCODE_TABLE[op];")?;
+            writeln!(w, "    ROWS[start as usize..end as usize]")?;
+            writeln!(w, "        .iter()")?;
+            writeln!(w, "        .filter(|r| instr.starts_with(r.pattern))")?;
+            writeln!(w, "        .max_by_key(|r| r.pattern.len())")?;
+            writeln!(w, "}}")?;
+            writeln!(w)?;
+            writeln!(w, "/// Returns the semantic summary for a decoded row.")?;
+            writeln!(w, "pub fn summary(row: &Row) -> &'static str {{")?;
+            writeln!(w, "    SUMMARIES[row.index]")?;
+            writeln!(w, "}}")?;
+            w.flush()?;
+
+            println!("Generated decoder with {} rows over {} encodings.", rows.len(), encodings.len());
+        }
+        Verb::Verify { quick, sample, checkpoint_every } => {
+            let runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
+            let encodings = runner.artifacts().iter().collect::<Vec<_>>();
+
+            let checkpoint_path = dir.join(SCRUB_CHECKPOINT_FILE);
+            let mut checkpoint: ScrubCheckpoint = if checkpoint_path.exists() {
+                println!("Resuming scrub from checkpoint...");
+                serde_json::from_reader(BufReader::new(File::open(&checkpoint_path)?))?
+            } else {
+                let sampled = if quick {
+                    let mut rng = StdRng::from_entropy();
+                    let mut indices = (0..encodings.len()).collect::<Vec<_>>();
+                    indices.shuffle(&mut rng);
+                    indices.truncate(sample.min(encodings.len()));
+                    Some(indices)
+                } else {
+                    None
+                };
+                ScrubCheckpoint { sampled, ..Default::default() }
+            };
+
+            let queue: Vec<usize> = checkpoint.sampled.clone()
+                .unwrap_or_else(|| (0..encodings.len()).collect());
+            checkpoint.total = queue.len();
+
+            while checkpoint.cursor < queue.len() {
+                let index = queue[checkpoint.cursor];
+                let original = encodings[index];
+
+                // Re-query the oracle for this single encoding. We process one at a
+                // time so the result never depends on batch composition (merging
+                // behaviour in `preprocess_encodings` made batched results
+                // non-deterministic across `--quick`/checkpoint sizes), then compare
+                // the freshly-queried output semantics -- not a whole-struct equality
+                // of the preprocessed encoding against the raw stored one, which
+                // never held because artifacts are stored pre-preprocess.
+                let reprocessed = preprocess_encodings(|| x64_kmod_ptrace_oracle(), vec![original.clone()]);
+                let consistent = reprocessed.len() == 1
+                    && output_semantics(&reprocessed[0]) == output_semantics(original);
+
+                if consistent {
+                    checkpoint.found += 1;
+                } else {
+                    checkpoint.missed += 1;
+                    println!("Encoding #{:5} ({:02X?}) is now inconsistent with its recorded semantics", index, original.instr().bytes());
+                }
+
+                checkpoint.cursor += 1;
+                if checkpoint.cursor % checkpoint_every == 0 || checkpoint.cursor == queue.len() {
+                    serde_json::to_writer(File::create(&checkpoint_path)?, &checkpoint)?;
+                    println!("Scrubbed {} / {} ({} inconsistent)", checkpoint.cursor, queue.len(), checkpoint.missed);
+                }
+            }
+
+            println!();
+            println!("Scrub complete: {} / {} consistent, {} inconsistent ({:3.1}%)", checkpoint.found, checkpoint.total, checkpoint.missed, checkpoint.missed as f64 / checkpoint.total.max(1) as f64 * 100.);
+            std::fs::remove_file(&checkpoint_path).ok();
+        }
+        Verb::Pack { path } => {
+            let runner = Work::<EnumWorker<X64Arch>, Instruction, _>::load(save_paths)?;
+            let mut encodings = runner.artifacts().iter().collect::<Vec<_>>();
+
+            // Order encodings by their smallest matching instruction before
+            // blocking them, so each block holds a contiguous instruction range
+            // rather than an arbitrary slice of artifact order. This keeps the
+            // `Unpack --instr` seek range-local -- the overwhelmingly common case
+            // lands in the right block. It is still only a hint, not exact: an
+            // encoding with a broad filter matches instructions well past its own
+            // smallest one and can fall in an earlier block, so callers that need
+            // certainty must decode the whole pack.
+            encodings.sort_by_cached_key(|e| {
+                e.filters()
+                    .into_iter()
+                    .map(|f| f.smallest_matching_instruction())
+                    .min()
+                    .expect("every encoding produces at least one filter")
+            });
+
+            let mut writer = BufWriter::new(File::create(&path)?);
+            let header = PackHeader { magic: PACK_MAGIC, version: PACK_VERSION, block_size: PACK_BLOCK_SIZE as u64 };
+            write_block(&mut writer, BLOCK_TAG_SUPER, &bincode::serialize(&header)?)?;
+
+            let mut index = PackIndex::default();
+            for chunk in encodings.chunks(PACK_BLOCK_SIZE) {
+                let smallest = chunk.iter()
+                    .flat_map(|e| e.filters())
+                    .map(|f| f.smallest_matching_instruction())
+                    .min()
+                    .expect("every encoding produces at least one filter");
+                let offset = writer.stream_position()?;
+                index.blocks.push((smallest, offset));
+
+                let payload = bincode::serialize(&chunk)?;
+                write_block(&mut writer, BLOCK_TAG_DATA, &payload)?;
+
+                if index.blocks.len() % 16 == 0 {
+                    println!("{} / {} encodings packed", index.blocks.len() * PACK_BLOCK_SIZE, encodings.len());
+                }
+            }
+
+            // Sort the index by instruction so `Unpack --instr` can binary search it.
+            index.blocks.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let index_offset = writer.stream_position()?;
+            write_block(&mut writer, BLOCK_TAG_INDEX, &bincode::serialize(&index)?)?;
+            writer.write_all(&index_offset.to_le_bytes())?;
+            writer.flush()?;
+
+            println!("Packed {} encodings into {} blocks", encodings.len(), index.blocks.len());
+        }
+        Verb::Unpack { path, instr } => {
+            let mut reader = File::open(&path)?;
+
+            // The first block is the superblock carrying the {magic, version,
+            // block_size} header; its CRC is tagged with BLOCK_TAG_SUPER.
+            let header: PackHeader = bincode::deserialize(&read_block(&mut reader, BLOCK_TAG_SUPER)?)?;
+            if header.magic != PACK_MAGIC {
+                return Err(format!("not a pack file (magic {:016x})", header.magic).into());
+            }
+            if header.version != PACK_VERSION {
+                return Err(format!("unsupported pack version {}", header.version).into());
+            }
+
+            let mut buf8 = [0u8; 8];
+            reader.seek(SeekFrom::End(-8))?;
+            reader.read_exact(&mut buf8)?;
+            let index_offset = u64::from_le_bytes(buf8);
+
+            reader.seek(SeekFrom::Start(index_offset))?;
+            let index: PackIndex = bincode::deserialize(&read_block(&mut reader, BLOCK_TAG_INDEX)?)?;
+            println!("Pack version {}, block size {}, {} data blocks", header.version, header.block_size, index.blocks.len());
+
+            if let Some(instr) = instr {
+                // Best-effort fast path: decompress only the block whose recorded
+                // smallest instruction is the greatest one <= the requested prefix.
+                // Blocks are range-partitioned (Pack sorts by smallest matching
+                // instruction), so this lands in the right block for the common
+                // case, but it is a seek hint, not an exact lookup -- an encoding
+                // with a broad filter can live in an earlier block. Decode the
+                // whole pack (omit --instr) for an exhaustive answer.
+                let wanted = Instruction::new(&parse_hex(&instr)?);
+                let pos = index.blocks.partition_point(|(smallest, _)| smallest <= &wanted);
+                if pos == 0 {
+                    return Err(format!("no block covers {:02X?}", wanted.bytes()).into());
+                }
+                let (smallest, offset) = &index.blocks[pos - 1];
+                reader.seek(SeekFrom::Start(*offset))?;
+                let block: Vec<liblisa::Encoding<X64Arch>> = bincode::deserialize(&read_block(&mut reader, BLOCK_TAG_DATA)?)?;
+                println!("Block @ {:>12} (from {:02X?}): {} encodings (best-effort: an encoding with a broad filter may live in an earlier block)", offset, smallest.bytes(), block.len());
+                for encoding in &block {
+                    println!("{}", encoding);
+                }
+                return Ok(());
+            }
+
+            let mut total = 0;
+            for (smallest, offset) in &index.blocks {
+                reader.seek(SeekFrom::Start(*offset))?;
+                let payload = read_block(&mut reader, BLOCK_TAG_DATA)?;
+                let block: Vec<liblisa::Encoding<X64Arch>> = bincode::deserialize(&payload)?;
+                println!("Block @ {:>12} (from {:02X?}): {} encodings", offset, smallest.bytes(), block.len());
+                total += block.len();
+            }
+
+            println!("Unpacked {} encodings", total);
+        }
     }
 
     Ok(())